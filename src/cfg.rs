@@ -0,0 +1,192 @@
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::fmt;
+
+/// A single parsed `#[cfg(...)]`-style flag: either a bare atom like `unix`,
+/// or a `key = "value"` pair like `feature = "foo"`.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub(crate) enum CfgFlag {
+    Atom(String),
+    KeyValue { key: String, value: String },
+}
+
+#[derive(Debug)]
+pub(crate) struct CfgParseError(String);
+
+impl fmt::Display for CfgParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed cfg flag: {:?}", self.0)
+    }
+}
+
+impl std::error::Error for CfgParseError {}
+
+impl CfgFlag {
+    /// Parses a single entry from `Crate::cfg`: splits on the first `=` into
+    /// a `KeyValue`, stripping surrounding quotes from the value, or treats
+    /// the whole entry as a bare `Atom` if there's no `=`.
+    pub(crate) fn parse(raw: &str) -> Result<CfgFlag, CfgParseError> {
+        if raw.is_empty() {
+            return Err(CfgParseError(raw.to_string()));
+        }
+
+        match raw.split_once('=') {
+            Some((key, value)) => {
+                if key.is_empty() {
+                    return Err(CfgParseError(raw.to_string()));
+                }
+                let value = value.trim_matches('"').to_string();
+                Ok(CfgFlag::KeyValue {
+                    key: key.to_string(),
+                    value,
+                })
+            }
+            None => Ok(CfgFlag::Atom(raw.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for CfgFlag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CfgFlag::Atom(atom) => write!(f, "{atom}"),
+            CfgFlag::KeyValue { key, value } => write!(f, "{key}=\"{value}\""),
+        }
+    }
+}
+
+/// A set of cfg flags to enable or disable. `disable` wins when a flag
+/// appears in both sets.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CfgDiff {
+    pub(crate) enable: FxHashSet<CfgFlag>,
+    pub(crate) disable: FxHashSet<CfgFlag>,
+}
+
+impl CfgDiff {
+    fn apply(&self, flags: &mut FxHashSet<CfgFlag>) {
+        for flag in &self.disable {
+            flags.remove(flag);
+        }
+        for flag in &self.enable {
+            if !self.disable.contains(flag) {
+                flags.insert(flag.clone());
+            }
+        }
+    }
+}
+
+/// A global diff applied to every crate, plus per-crate diffs keyed by
+/// `display_name`, layered on top of it.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct CfgOverrides {
+    pub(crate) global: CfgDiff,
+    pub(crate) per_crate: FxHashMap<String, CfgDiff>,
+}
+
+impl CfgOverrides {
+    /// Parses `cfg`, then applies the global diff and, if present, the
+    /// per-crate diff for `display_name`.
+    pub(crate) fn resolve(
+        &self,
+        display_name: &str,
+        cfg: &[String],
+    ) -> Result<FxHashSet<CfgFlag>, CfgParseError> {
+        let mut flags: FxHashSet<CfgFlag> = cfg
+            .iter()
+            .map(|raw| CfgFlag::parse(raw))
+            .collect::<Result<_, _>>()?;
+
+        self.global.apply(&mut flags);
+        if let Some(diff) = self.per_crate.get(display_name) {
+            diff.apply(&mut flags);
+        }
+
+        Ok(flags)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_bare_atom() {
+        assert_eq!(
+            CfgFlag::parse("unix").unwrap(),
+            CfgFlag::Atom("unix".to_string())
+        );
+    }
+
+    #[test]
+    fn parses_a_key_value_and_strips_quotes() {
+        assert_eq!(
+            CfgFlag::parse(r#"feature="foo""#).unwrap(),
+            CfgFlag::KeyValue {
+                key: "feature".to_string(),
+                value: "foo".to_string(),
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_empty_input() {
+        assert!(CfgFlag::parse("").is_err());
+    }
+
+    #[test]
+    fn rejects_an_empty_key() {
+        assert!(CfgFlag::parse(r#"="foo""#).is_err());
+    }
+
+    #[test]
+    fn disable_wins_when_a_flag_is_in_both_sets() {
+        let flag = CfgFlag::Atom("test".to_string());
+        let mut diff = CfgDiff::default();
+        diff.enable.insert(flag.clone());
+        diff.disable.insert(flag.clone());
+
+        let mut flags = FxHashSet::default();
+        diff.apply(&mut flags);
+
+        assert!(!flags.contains(&flag));
+    }
+
+    #[test]
+    fn disable_removes_a_preexisting_flag() {
+        let flag = CfgFlag::Atom("test".to_string());
+        let mut diff = CfgDiff::default();
+        diff.disable.insert(flag.clone());
+
+        let mut flags: FxHashSet<CfgFlag> = std::iter::once(flag).collect();
+        diff.apply(&mut flags);
+
+        assert!(flags.is_empty());
+    }
+
+    #[test]
+    fn per_crate_override_only_affects_its_own_crate() {
+        let mut overrides = CfgOverrides::default();
+        overrides
+            .global
+            .enable
+            .insert(CfgFlag::Atom("unix".to_string()));
+        overrides
+            .per_crate
+            .entry("foo".to_string())
+            .or_default()
+            .disable
+            .insert(CfgFlag::Atom("unix".to_string()));
+
+        let foo = overrides.resolve("foo", &[]).unwrap();
+        assert!(!foo.contains(&CfgFlag::Atom("unix".to_string())));
+
+        let bar = overrides.resolve("bar", &[]).unwrap();
+        assert!(bar.contains(&CfgFlag::Atom("unix".to_string())));
+    }
+
+    #[test]
+    fn resolve_propagates_a_malformed_cfg_entry() {
+        let overrides = CfgOverrides::default();
+        assert!(overrides.resolve("foo", &[String::new()]).is_err());
+    }
+}
@@ -0,0 +1,258 @@
+use crate::project::JsonProject;
+
+use std::fmt;
+
+/// Dependency edges between crates, stored as plain indices into
+/// `JsonProject::crates` rather than names, so cycle detection and
+/// topological ordering can run as simple graph algorithms over small
+/// integers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct CrateGraph {
+    /// `edges[i]` is the list of crate indices that crate `i` depends on.
+    edges: Vec<Vec<usize>>,
+}
+
+/// A `Dep::crate_index` that doesn't point at a real entry in
+/// `JsonProject::crates`, e.g. from a stale or hand-edited `rust-project.json`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct InvalidDepIndex {
+    pub(crate) crate_index: usize,
+    pub(crate) dep_index: usize,
+    pub(crate) crate_count: usize,
+}
+
+impl fmt::Display for InvalidDepIndex {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "crate {} has a dep pointing at crate index {}, but the project only has {} crates",
+            self.crate_index, self.dep_index, self.crate_count
+        )
+    }
+}
+
+impl std::error::Error for InvalidDepIndex {}
+
+/// A detected dependency cycle, reported as the chain of crate indices from
+/// the node the back-edge points at, along the DFS stack to the back-edge's
+/// source, and back to that first node again (so it appears at both ends).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Cycle {
+    pub(crate) chain: Vec<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Color {
+    White,
+    Gray,
+    Black,
+}
+
+impl CrateGraph {
+    pub(crate) fn new(project: &JsonProject) -> Result<CrateGraph, InvalidDepIndex> {
+        let crate_count = project.crates.len();
+        let mut edges = Vec::with_capacity(crate_count);
+        for (crate_index, krate) in project.crates.iter().enumerate() {
+            let mut deps = Vec::with_capacity(krate.deps.len());
+            for dep in &krate.deps {
+                if dep.crate_index >= crate_count {
+                    return Err(InvalidDepIndex {
+                        crate_index,
+                        dep_index: dep.crate_index,
+                        crate_count,
+                    });
+                }
+                deps.push(dep.crate_index);
+            }
+            edges.push(deps);
+        }
+        Ok(CrateGraph { edges })
+    }
+
+    pub(crate) fn node_count(&self) -> usize {
+        self.edges.len()
+    }
+
+    pub(crate) fn edge_count(&self) -> usize {
+        self.edges.iter().map(Vec::len).sum()
+    }
+
+    /// Out-degree (fan-out) of each crate, indexed the same as `edges`.
+    pub(crate) fn fan_out(&self) -> Vec<usize> {
+        self.edges.iter().map(Vec::len).collect()
+    }
+
+    /// Finds dependency cycles via a three-color DFS: a node is `White` until
+    /// visited, `Gray` while it's on the current DFS stack, and `Black` once
+    /// all of its dependencies have been fully explored. Encountering a `Gray`
+    /// node means we've found a back-edge, i.e. a cycle.
+    pub(crate) fn find_cycles(&self) -> Vec<Cycle> {
+        let mut color = vec![Color::White; self.edges.len()];
+        let mut stack = Vec::new();
+        let mut cycles = Vec::new();
+
+        for start in 0..self.edges.len() {
+            if color[start] == Color::White {
+                self.dfs_find_cycles(start, &mut color, &mut stack, &mut cycles);
+            }
+        }
+
+        cycles
+    }
+
+    fn dfs_find_cycles(
+        &self,
+        node: usize,
+        color: &mut [Color],
+        stack: &mut Vec<usize>,
+        cycles: &mut Vec<Cycle>,
+    ) {
+        color[node] = Color::Gray;
+        stack.push(node);
+
+        for &dep in &self.edges[node] {
+            match color[dep] {
+                Color::White => self.dfs_find_cycles(dep, color, stack, cycles),
+                Color::Gray => {
+                    let start = stack.iter().position(|&n| n == dep).unwrap();
+                    let mut chain = stack[start..].to_vec();
+                    chain.push(dep);
+                    cycles.push(Cycle { chain });
+                }
+                Color::Black => {}
+            }
+        }
+
+        stack.pop();
+        color[node] = Color::Black;
+    }
+
+    /// Produces a topological load order via Kahn's algorithm, where a node's
+    /// "in-degree" is its number of *unresolved* dependencies: we repeatedly
+    /// pop zero-in-degree nodes (crates whose deps are all already loaded)
+    /// and decrement the in-degree of whatever depends on them.
+    ///
+    /// `prioritize` marks nodes (e.g. non-workspace-member/sysroot crates)
+    /// that should be drained from the ready queue ahead of everything else,
+    /// so loading can start on the sysroot before first-party crates. If a
+    /// cycle is present, the returned order omits whatever nodes it couldn't
+    /// resolve; callers should check `find_cycles` first.
+    pub(crate) fn topological_order(&self, prioritize: &[bool]) -> Vec<usize> {
+        let mut in_degree: Vec<usize> = self.edges.iter().map(Vec::len).collect();
+
+        let mut dependents = vec![Vec::new(); self.edges.len()];
+        for (node, deps) in self.edges.iter().enumerate() {
+            for &dep in deps {
+                dependents[dep].push(node);
+            }
+        }
+
+        let mut priority_queue: std::collections::VecDeque<usize> = std::collections::VecDeque::new();
+        let mut queue: std::collections::VecDeque<usize> = std::collections::VecDeque::new();
+        for (node, &degree) in in_degree.iter().enumerate() {
+            if degree == 0 {
+                if prioritize.get(node).copied().unwrap_or(false) {
+                    priority_queue.push_back(node);
+                } else {
+                    queue.push_back(node);
+                }
+            }
+        }
+
+        let mut order = Vec::with_capacity(self.edges.len());
+        while let Some(node) = priority_queue.pop_front().or_else(|| queue.pop_front()) {
+            order.push(node);
+            for &dependent in &dependents[node] {
+                in_degree[dependent] -= 1;
+                if in_degree[dependent] == 0 {
+                    if prioritize.get(dependent).copied().unwrap_or(false) {
+                        priority_queue.push_back(dependent);
+                    } else {
+                        queue.push_back(dependent);
+                    }
+                }
+            }
+        }
+
+        order
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::project::{Crate, Dep, Sysroot};
+    use std::path::PathBuf;
+
+    fn project_with_deps(deps: Vec<Vec<usize>>) -> JsonProject {
+        let crates = deps
+            .into_iter()
+            .map(|dep_indices| Crate {
+                deps: dep_indices
+                    .into_iter()
+                    .map(|crate_index| Dep {
+                        crate_index,
+                        name: String::new(),
+                    })
+                    .collect(),
+                ..Default::default()
+            })
+            .collect();
+        JsonProject {
+            sysroot: Sysroot {
+                sysroot: PathBuf::new(),
+                sysroot_src: None,
+            },
+            crates,
+            runnables: Vec::new(),
+            generated: String::new(),
+        }
+    }
+
+    #[test]
+    fn finds_a_cycle() {
+        // 0 -> 1 -> 2 -> 0
+        let project = project_with_deps(vec![vec![1], vec![2], vec![0]]);
+        let graph = CrateGraph::new(&project).unwrap();
+
+        let cycles = graph.find_cycles();
+        assert_eq!(cycles.len(), 1);
+        assert_eq!(cycles[0].chain, vec![0, 1, 2, 0]);
+    }
+
+    #[test]
+    fn topological_order_respects_diamond_dependencies() {
+        // 0 depends on 1 and 2; both depend on 3.
+        let project = project_with_deps(vec![vec![1, 2], vec![3], vec![3], vec![]]);
+        let graph = CrateGraph::new(&project).unwrap();
+        assert!(graph.find_cycles().is_empty());
+
+        let order = graph.topological_order(&[false, false, false, false]);
+        assert_eq!(order.len(), 4);
+
+        let position = |node: usize| order.iter().position(|&n| n == node).unwrap();
+        assert!(position(3) < position(1));
+        assert!(position(3) < position(2));
+        assert!(position(1) < position(0));
+        assert!(position(2) < position(0));
+    }
+
+    #[test]
+    fn topological_order_drains_prioritized_nodes_first() {
+        // 0 and 1 are both ready to load; 1 is prioritized (e.g. sysroot).
+        let project = project_with_deps(vec![vec![], vec![]]);
+        let graph = CrateGraph::new(&project).unwrap();
+
+        let order = graph.topological_order(&[false, true]);
+        assert_eq!(order, vec![1, 0]);
+    }
+
+    #[test]
+    fn rejects_an_out_of_range_dep_index() {
+        let project = project_with_deps(vec![vec![5]]);
+        let err = CrateGraph::new(&project).unwrap_err();
+        assert_eq!(err.crate_index, 0);
+        assert_eq!(err.dep_index, 5);
+        assert_eq!(err.crate_count, 1);
+    }
+}
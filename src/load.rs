@@ -0,0 +1,226 @@
+use rayon::iter::{IntoParallelIterator, IntoParallelRefIterator, ParallelIterator};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    io,
+    path::PathBuf,
+    time::{Duration, Instant},
+};
+use walkdir::WalkDir;
+
+/// How much work deduplicating shared source files across crates actually
+/// saved, versus the naive approach of walking every crate's directory
+/// independently.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct DedupStats {
+    /// Number of (crate, file) pairs that would have been read under the
+    /// naive per-crate walk, counting a shared file once per crate.
+    pub(crate) naive_files: usize,
+    pub(crate) naive_bytes: u64,
+    /// Number of distinct canonical paths actually read.
+    pub(crate) unique_files: usize,
+    pub(crate) unique_bytes: u64,
+}
+
+impl DedupStats {
+    pub(crate) fn files_saved(&self) -> usize {
+        self.naive_files.saturating_sub(self.unique_files)
+    }
+
+    pub(crate) fn bytes_saved(&self) -> u64 {
+        self.naive_bytes.saturating_sub(self.unique_bytes)
+    }
+}
+
+/// A crate's file set, as rust-analyzer would define it: the union of
+/// `roots` (`root_module.parent()` plus any `source.include_dirs`), walked
+/// recursively, minus any subtree whose prefix matches an entry in
+/// `excludes`.
+pub(crate) struct CrateWalk {
+    pub(crate) name: String,
+    pub(crate) roots: Vec<PathBuf>,
+    pub(crate) excludes: FxHashSet<PathBuf>,
+}
+
+/// One crate's outcome from `load_dedup`: its file contents (for whichever
+/// paths read cleanly), every per-file IO error encountered along the way,
+/// and the wall time from the start of this crate's walk until its file set
+/// was assembled. Since files are read once for all crates together, that
+/// span includes the shared parallel read of every crate's unique files.
+#[derive(Debug, Default)]
+pub(crate) struct CrateLoad {
+    pub(crate) files: Vec<String>,
+    pub(crate) errors: Vec<String>,
+    pub(crate) elapsed: Duration,
+}
+
+/// Walks every crate's file set to enumerate its candidate files, then reads
+/// each unique canonicalized path exactly once in parallel, fanning the
+/// contents back out to every crate that references it.
+pub(crate) fn load_dedup(crates: Vec<CrateWalk>) -> (FxHashMap<String, CrateLoad>, DedupStats) {
+    let per_crate_paths: Vec<(String, Instant, Vec<PathBuf>)> = crates
+        .par_iter()
+        .map(|krate| {
+            let start = Instant::now();
+            let excludes = &krate.excludes;
+            // A crate's `include_dirs` can overlap with (or nest under) its
+            // `root_module.parent()`, so dedupe within the crate's own roots
+            // before counting its files.
+            let paths: FxHashSet<PathBuf> = krate
+                .roots
+                .iter()
+                .flat_map(|root| {
+                    WalkDir::new(root)
+                        .into_iter()
+                        .filter_entry(|entry| {
+                            !excludes
+                                .iter()
+                                .any(|excluded| entry.path().starts_with(excluded))
+                        })
+                        .filter_map(|e| e.ok())
+                        .filter(|e| e.file_type().is_file())
+                        .filter_map(|e| e.path().canonicalize().ok())
+                })
+                .collect();
+            (krate.name.clone(), start, paths.into_iter().collect())
+        })
+        .collect();
+
+    let mut unique_paths: FxHashSet<PathBuf> = FxHashSet::default();
+    for (_, _, paths) in &per_crate_paths {
+        unique_paths.extend(paths.iter().cloned());
+    }
+
+    let contents: FxHashMap<PathBuf, Result<String, io::Error>> = unique_paths
+        .into_par_iter()
+        .map(|path| {
+            let contents = std::fs::read_to_string(&path);
+            (path, contents)
+        })
+        .collect();
+
+    let mut stats = DedupStats {
+        unique_files: contents.len(),
+        unique_bytes: contents
+            .values()
+            .filter_map(|r| r.as_ref().ok())
+            .map(|s| s.len() as u64)
+            .sum(),
+        ..Default::default()
+    };
+
+    let projects: FxHashMap<String, CrateLoad> = per_crate_paths
+        .into_iter()
+        .map(|(name, start, paths)| {
+            stats.naive_files += paths.len();
+
+            let mut files = Vec::with_capacity(paths.len());
+            let mut errors = Vec::new();
+            for path in &paths {
+                match &contents[path] {
+                    Ok(s) => {
+                        stats.naive_bytes += s.len() as u64;
+                        files.push(s.clone());
+                    }
+                    Err(e) => errors.push(format!("{}: {e}", path.display())),
+                }
+            }
+
+            let load = CrateLoad {
+                files,
+                errors,
+                elapsed: start.elapsed(),
+            };
+            (name, load)
+        })
+        .collect();
+
+    (projects, stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A fresh, empty directory under the OS temp dir, unique per call so
+    /// concurrently-run tests don't collide.
+    fn temp_dir(tag: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("load-dedup-test-{tag}-{nanos}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn dedup_stats_saved_saturate_at_zero() {
+        let stats = DedupStats {
+            naive_files: 1,
+            naive_bytes: 10,
+            unique_files: 3,
+            unique_bytes: 30,
+        };
+        assert_eq!(stats.files_saved(), 0);
+        assert_eq!(stats.bytes_saved(), 0);
+    }
+
+    #[test]
+    fn reads_a_file_shared_by_two_crates_exactly_once() {
+        let dir = temp_dir("shared");
+        let contents = "fn shared() {}";
+        std::fs::write(dir.join("shared.rs"), contents).unwrap();
+
+        let crates = vec![
+            CrateWalk {
+                name: "a".to_string(),
+                roots: vec![dir.clone()],
+                excludes: Default::default(),
+            },
+            CrateWalk {
+                name: "b".to_string(),
+                roots: vec![dir.clone()],
+                excludes: Default::default(),
+            },
+        ];
+
+        let (projects, stats) = load_dedup(crates);
+
+        assert_eq!(stats.unique_files, 1);
+        assert_eq!(stats.naive_files, 2);
+        assert_eq!(stats.files_saved(), 1);
+        assert_eq!(stats.unique_bytes, contents.len() as u64);
+        assert_eq!(stats.bytes_saved(), contents.len() as u64);
+
+        for name in ["a", "b"] {
+            let load = &projects[name];
+            assert_eq!(load.files, vec![contents.to_string()]);
+            assert!(load.errors.is_empty());
+        }
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn captures_per_file_errors_without_dropping_readable_files() {
+        let dir = temp_dir("errors");
+        std::fs::write(dir.join("ok.rs"), "fn ok() {}").unwrap();
+        // Not valid UTF-8, so read_to_string fails even though the path
+        // canonicalizes and walks fine.
+        std::fs::write(dir.join("invalid.rs"), [0xFF, 0xFE, 0xFD]).unwrap();
+
+        let crates = vec![CrateWalk {
+            name: "a".to_string(),
+            roots: vec![dir.clone()],
+            excludes: Default::default(),
+        }];
+
+        let (projects, _stats) = load_dedup(crates);
+        let load = &projects["a"];
+
+        assert_eq!(load.files, vec!["fn ok() {}".to_string()]);
+        assert_eq!(load.errors.len(), 1);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -1,12 +1,24 @@
+mod cfg;
+mod graph;
+mod load;
+mod project;
+mod report;
+mod sysroot;
+mod watch;
+
+use cfg::{CfgDiff, CfgFlag, CfgOverrides};
+use graph::CrateGraph;
+use load::{load_dedup, CrateLoad, CrateWalk};
+use project::JsonProject;
+use report::{BenchmarkReport, CrateReport, Format, SysrootReport};
+use sysroot::{check_proc_macros, load_sysroot};
+
 use clap::{Parser, Subcommand};
-use rayon::iter::{ParallelBridge, ParallelIterator};
-use rustc_hash::{FxHashMap, FxHashSet};
-use serde::{Deserialize, Serialize};
+use rustc_hash::FxHashMap;
 use std::{
     path::{Path, PathBuf},
     time::Instant,
 };
-use walkdir::WalkDir;
 
 #[derive(clap::Parser, Debug, PartialEq)]
 struct Opt {
@@ -17,22 +29,114 @@ struct Opt {
 #[derive(Subcommand, Debug, PartialEq)]
 enum Command {
     // path to rust-project.json
-    Json { path: PathBuf },
+    Json {
+        path: PathBuf,
+        #[command(flatten)]
+        cfg: CfgArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
     // path to manifest
-    Cargo { path: PathBuf },
+    Cargo {
+        path: PathBuf,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    // path to rust-project.json; resolves `Crate::deps` into a `CrateGraph`
+    // and reports graph stats instead of just reading files.
+    Graph {
+        path: PathBuf,
+        #[command(flatten)]
+        cfg: CfgArgs,
+        #[command(flatten)]
+        output: OutputArgs,
+    },
+    // path to rust-project.json; does the initial bulk load, then watches
+    // crate roots and re-reads only what changes.
+    Watch { path: PathBuf },
+}
+
+/// CLI surface for where/how a finished `BenchmarkReport` is emitted.
+#[derive(clap::Args, Debug, PartialEq, Default)]
+struct OutputArgs {
+    /// Write the structured report as JSON to this path.
+    #[arg(long)]
+    output: Option<PathBuf>,
+    /// How to print the report: `human` to stderr, `json` to stdout.
+    #[arg(long, value_enum, default_value = "human")]
+    format: Format,
+}
+
+impl OutputArgs {
+    fn emit(&self, report: &BenchmarkReport) -> Result<(), anyhow::Error> {
+        match self.format {
+            Format::Human => report.print_human(),
+            Format::Json => println!("{}", serde_json::to_string_pretty(report)?),
+        }
+        if let Some(path) = &self.output {
+            report.write_json(path)?;
+        }
+        Ok(())
+    }
+}
+
+/// CLI surface for `CfgOverrides`: a wildcard diff applied to every crate,
+/// plus `--cfg-add-for` entries layered on top of one crate only.
+#[derive(clap::Args, Debug, PartialEq, Default)]
+struct CfgArgs {
+    /// Add a cfg flag (e.g. `unix` or `feature="foo"`) to every crate.
+    #[arg(long = "cfg-add")]
+    cfg_add: Vec<String>,
+    /// Remove a cfg flag from every crate.
+    #[arg(long = "cfg-remove")]
+    cfg_remove: Vec<String>,
+    /// Add a cfg flag to one crate only, as `<crate>=<flag>`.
+    #[arg(long = "cfg-add-for", value_parser = parse_cfg_add_for)]
+    cfg_add_for: Vec<(String, String)>,
+}
+
+fn parse_cfg_add_for(s: &str) -> Result<(String, String), String> {
+    s.split_once('=')
+        .map(|(krate, flag)| (krate.to_string(), flag.to_string()))
+        .ok_or_else(|| format!("expected `<crate>=<flag>`, got {s:?}"))
+}
+
+impl CfgArgs {
+    fn into_overrides(self) -> Result<CfgOverrides, anyhow::Error> {
+        let mut global = CfgDiff::default();
+        for raw in &self.cfg_add {
+            global.enable.insert(CfgFlag::parse(raw)?);
+        }
+        for raw in &self.cfg_remove {
+            global.disable.insert(CfgFlag::parse(raw)?);
+        }
+
+        let mut per_crate: FxHashMap<String, CfgDiff> = FxHashMap::default();
+        for (krate, raw) in &self.cfg_add_for {
+            per_crate
+                .entry(krate.clone())
+                .or_default()
+                .enable
+                .insert(CfgFlag::parse(raw)?);
+        }
+
+        Ok(CfgOverrides { global, per_crate })
+    }
 }
 
 fn main() -> Result<(), anyhow::Error> {
     let opt = Opt::parse();
     match opt.command {
-        Command::Json { path } => handle_project_json(&path),
-        Command::Cargo { path } => handle_cargo(&path),
+        Command::Json { path, cfg, output } => handle_project_json(&path, cfg, output),
+        Command::Cargo { path, output } => handle_cargo(&path, output),
+        Command::Graph { path, cfg, output } => handle_graph(&path, cfg, output),
+        Command::Watch { path } => handle_watch(&path),
     }?;
 
     Ok(())
 }
 
-fn handle_cargo(path: &Path) -> Result<(), anyhow::Error> {
+fn handle_cargo(path: &Path, output: OutputArgs) -> Result<(), anyhow::Error> {
     let instant = Instant::now();
     let mut cmd = cargo_metadata::MetadataCommand::new();
     cmd.manifest_path(path);
@@ -42,272 +146,233 @@ fn handle_cargo(path: &Path) -> Result<(), anyhow::Error> {
         instant.elapsed().as_millis()
     );
 
-    let instant = Instant::now();
-    let _projects: FxHashMap<String, Result<Vec<String>, std::io::Error>> = metadata
+    let crates: Vec<CrateWalk> = metadata
         .packages
         .into_iter()
         .flat_map(|package| package.targets)
         .filter_map(|target| {
             let root = target.src_path.parent();
             match root {
-                Some(path) => Some((target.name.clone(), path.to_path_buf())),
+                Some(path) => Some(CrateWalk {
+                    name: target.name.clone(),
+                    roots: vec![path.to_path_buf()],
+                    excludes: Default::default(),
+                }),
                 None => None,
             }
         })
-        .par_bridge()
-        .map(|(name, dir)| {
-            let dir_contents = WalkDir::new(dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_file())
-                .map(|f| std::fs::read_to_string(f.path()))
-                .collect::<Result<Vec<String>, std::io::Error>>();
-            (name, dir_contents)
-        })
         .collect();
 
-    eprintln!("Done loading: {}ms", instant.elapsed().as_millis());
+    let instant = Instant::now();
+    let (projects, stats) = load_dedup(crates);
+    let wall_time = instant.elapsed();
 
-    Ok(())
+    // cargo-metadata doesn't distinguish workspace members from sysroot/
+    // third-party crates the way `rust-project.json` does.
+    let report = BenchmarkReport::new(
+        "cargo",
+        wall_time,
+        crate_reports(&projects, |_| true),
+        &stats,
+        None,
+    );
+    output.emit(&report)
 }
 
-fn handle_project_json(path: &Path) -> Result<(), anyhow::Error> {
+fn handle_project_json(
+    path: &Path,
+    cfg: CfgArgs,
+    output: OutputArgs,
+) -> Result<(), anyhow::Error> {
     let s = std::fs::read_to_string(path)?;
     let project: JsonProject = serde_json::from_str(&s)?;
+    let overrides = cfg.into_overrides()?;
+    report_cfg(&project, &overrides)?;
 
-    let instant = Instant::now();
-    let projects: FxHashMap<String, Result<Vec<String>, std::io::Error>> = project
+    let crates: Vec<CrateWalk> = project
         .crates
         .iter()
-        .filter_map(|krate| {
-            let root = krate.root_module.parent();
-            match root {
-                Some(path) => Some((krate.display_name.clone().unwrap(), path.to_path_buf())),
-                None => None,
-            }
-        })
-        .par_bridge()
-        .map(|(name, dir)| {
-            let dir_contents = WalkDir::new(dir)
-                .into_iter()
-                .filter_map(|e| e.ok())
-                .filter(|e| e.file_type().is_file())
-                .map(|f| std::fs::read_to_string(f.path()))
-                .collect::<Result<Vec<String>, std::io::Error>>();
-            (name, dir_contents)
+        .enumerate()
+        .map(|(index, krate)| CrateWalk {
+            name: crate_display_name(&project, index).to_string(),
+            roots: krate.source_roots(),
+            excludes: krate.exclude_dirs().clone(),
         })
         .collect();
 
-    eprintln!("Done loading: {}", instant.elapsed().as_millis());
-    eprintln!("{:?}", projects.keys());
+    let instant = Instant::now();
+    let (projects, stats) = load_dedup(crates);
+    let wall_time = instant.elapsed();
 
-    Ok(())
-}
+    let sysroot = report_sysroot(&project);
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub(crate) struct JsonProject {
-    #[serde(flatten)]
-    pub(crate) sysroot: Sysroot,
-
-    /// The set of crates comprising the project.
-    ///
-    /// Must include all transitive dependencies as well as sysroot crate (libstd,
-    /// libcore, etc.).
-    pub(crate) crates: Vec<Crate>,
-    pub(crate) runnables: Vec<Runnable>,
-    pub(crate) generated: String,
+    let members = member_lookup(&project);
+    let report = BenchmarkReport::new(
+        "json",
+        wall_time,
+        crate_reports(&projects, |name| {
+            members.get(name).copied().unwrap_or(true)
+        }),
+        &stats,
+        Some(sysroot),
+    );
+    output.emit(&report)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq, Eq)]
-pub(crate) struct Crate {
-    /// Optional crate name used for display purposes; has no semantic significance.
-    pub(crate) display_name: Option<String>,
-    /// The path to the root module of the crate.
-    pub(crate) root_module: PathBuf,
-    pub(crate) edition: Edition,
-    pub(crate) deps: Vec<Dep>,
-    /// Should this crate be treated as a member of
-    /// current "workspace".
-    ///
-    /// By default, inferred from the `root_module`
-    /// (members are the crates which reside inside
-    /// the directory opened in the editor).
-    ///
-    /// Set this to `false` for things like standard
-    /// library and 3rd party crates to enable
-    /// performance optimizations (rust-analyzer
-    /// assumes that non-member crates don't change).
-    pub(crate) is_workspace_member: bool,
-    /// Optionally specify the (super)set of `.rs`
-    /// files comprising this crate.
-    ///
-    /// By default, rust-analyzer assumes that only
-    /// files under `root_module.parent` can belong
-    /// to a crate. `include_dirs` are included
-    /// recursively, unless a subdirectory is in
-    /// `exclude_dirs`.
-    ///
-    /// Different crates can share the same `source`.
-    ///
-    /// If two crates share an `.rs` file in common,
-    /// they *must* have the same `source`.
-    /// rust-analyzer assumes that files from one
-    /// source can't refer to files in another source.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) source: Option<Source>,
-    /// The set of cfgs activated for a given crate.
-    ///
-    /// With how fb imports crates into fbsource/third-party,
-    /// the answer is "all of them".
-    pub(crate) cfg: Vec<String>,
-    /// The target triple for a given crate.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) target: Option<String>,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) build: Option<Build>,
-    /// Environment for the crate, often used by `env!`.
-    pub(crate) env: FxHashMap<String, String>,
-    /// Whether the crate is a proc-macro crate/
-    pub(crate) is_proc_macro: bool,
-    /// For proc-macro crates, path to compiled
-    /// proc-macro (.so, .dylib, or .dll. depends on the platform.)
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) proc_macro_dylib_path: Option<PathBuf>,
+/// Builds a `display_name -> is_workspace_member` lookup for a project's
+/// crates, to tell first-party loads apart from sysroot/third-party ones.
+fn member_lookup(project: &JsonProject) -> FxHashMap<String, bool> {
+    project
+        .crates
+        .iter()
+        .filter_map(|krate| {
+            krate
+                .display_name
+                .clone()
+                .map(|name| (name, krate.is_workspace_member))
+        })
+        .collect()
 }
 
-/// Build system-specific additions the `rust-project.json`.
-///
-/// rust-analyzer encodes Cargo-specific knowledge in features
-/// such as flycheck or runnable and constructs Cargo-specific commands
-/// on the fly. This is a reasonable decision on its part, as most people
-/// use Cargo. However, to support equivalent functionality with non-Cargo
-/// build systems in rust-analyzer, this struct encodes pre-defined runnables
-/// and other bits of metadata. Below is an example of `TargetSpec` in JSON:
-///
-/// ```json
-/// "target_spec": {
-///     "manifest_file": "/Users/dbarsky/fbsource/fbcode/buck2/integrations/rust-project/TARGETS",
-///     "target_label": "fbcode//buck2/integrations/rust-project:rust-project",
-///     "target_kind": "bin",
-///     "runnables": {
-///         "check": [
-///            "build",
-///            "fbcode//buck2/integrations/rust-project:rust-project"
-///         ],
-///         "run": [
-///             "run",
-///             "fbcode//buck2/integrations/rust-project:rust-project"
-///         ],
-///         "test": [
-///             "test",
-///             "fbcode//buck2/integrations/rust-project:rust-project",
-///             "--",
-///             "{test_id}",
-///             "--print-passing-details"
-///         ]
-///     },
-///     "flycheck_command": [
-///         "build",
-///         "fbcode//buck2/integrations/rust-project:rust-project"
-///     ]
-/// }
-/// ```
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
-pub(crate) struct Build {
-    pub(crate) label: String,
-    /// `build_file` corresponds to the `BUCK`/`TARGETS` file.
-    pub(crate) build_file: PathBuf,
-    pub(crate) target_kind: TargetKind,
+/// Turns the raw per-crate load results into `CrateReport`s.
+fn crate_reports(
+    projects: &FxHashMap<String, CrateLoad>,
+    is_workspace_member: impl Fn(&str) -> bool,
+) -> Vec<CrateReport> {
+    projects
+        .iter()
+        .map(|(name, load)| CrateReport {
+            name: name.clone(),
+            is_workspace_member: is_workspace_member(name),
+            file_count: load.files.len(),
+            bytes_read: load.files.iter().map(|f| f.len() as u64).sum(),
+            read_time_ms: load.elapsed.as_millis(),
+            errors: load.errors.clone(),
+        })
+        .collect()
 }
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
-#[serde(rename_all = "camelCase")]
-pub(crate) enum TargetKind {
-    #[default]
-    Bin,
-    /// Any kind of Cargo lib crate-type (dylib, rlib, proc-macro, ...).
-    Lib,
-    Example,
-    Test,
-    Bench,
-    BuildScript,
-    Other,
-}
+/// Parses each crate's `cfg` entries, applies `overrides`, and prints the
+/// resulting active cfg set, so the benchmark can model how cfg selection
+/// would gate which modules rust-analyzer actually parses.
+fn report_cfg(project: &JsonProject, overrides: &CfgOverrides) -> Result<(), anyhow::Error> {
+    for krate in &project.crates {
+        let name = krate.display_name.as_deref().unwrap_or("<unnamed>");
+        let resolved = overrides.resolve(name, &krate.cfg)?;
+        let mut flags: Vec<String> = resolved.iter().map(ToString::to_string).collect();
+        flags.sort();
+        eprintln!("cfg[{name}]: {}", flags.join(", "));
+    }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct Runnable {
-    pub program: String,
-    pub args: Vec<String>,
-    pub cwd: PathBuf,
-    pub kind: RunnableKind,
+    Ok(())
 }
 
-#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
-#[serde(rename_all = "camelCase")]
-pub enum RunnableKind {
-    Check,
-    Flycheck,
-    Run,
-    TestOne,
-}
+/// Reads the sysroot's `std`/`core`/... sources, times that load separately
+/// from first-party crates, and checks proc-macro dylib and proc-macro-srv
+/// availability, folding all of it into a `SysrootReport` that becomes part
+/// of the structured `BenchmarkReport` (printed via `print_human`, not here).
+fn report_sysroot(project: &JsonProject) -> SysrootReport {
+    let sysroot_load = load_sysroot(&project.sysroot);
+    let proc_macros = check_proc_macros(&project.crates, &project.sysroot);
 
-#[derive(Serialize, Deserialize, Debug, Default, Clone, PartialEq, Eq)]
-#[serde(rename = "edition")]
-pub(crate) enum Edition {
-    #[serde(rename = "2015")]
-    Edition2015,
-    #[serde(rename = "2018")]
-    Edition2018,
-    #[default]
-    #[serde(rename = "2021")]
-    Edition2021,
+    SysrootReport {
+        load_time_ms: sysroot_load.elapsed.as_millis(),
+        file_count: sysroot_load.stats.unique_files,
+        bytes_read: sysroot_load.stats.unique_bytes,
+        proc_macro_dylibs_present: proc_macros.present,
+        proc_macro_dylibs_missing: proc_macros.missing,
+        proc_macro_srv_path: proc_macros.server_path,
+    }
 }
 
-/// An optional set of Rust files that comprise the crate.
-///
-/// By default, rust-analyzer assumes that only files under
-/// `Crate::root_module` can belong to a crate. `include_dirs`
-/// are included recursively, unless a subdirectory is
-/// specified in `include_dirs`.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
-pub(crate) struct Source {
-    pub(crate) include_dirs: FxHashSet<PathBuf>,
-    pub(crate) exclude_dirs: FxHashSet<PathBuf>,
+fn handle_graph(path: &Path, cfg: CfgArgs, output: OutputArgs) -> Result<(), anyhow::Error> {
+    let s = std::fs::read_to_string(path)?;
+    let project: JsonProject = serde_json::from_str(&s)?;
+    let overrides = cfg.into_overrides()?;
+    report_cfg(&project, &overrides)?;
+
+    let graph = CrateGraph::new(&project)?;
+    eprintln!(
+        "CrateGraph: {} nodes, {} edges",
+        graph.node_count(),
+        graph.edge_count()
+    );
+
+    let fan_out = graph.fan_out();
+    let max_fan_out = fan_out.iter().copied().max().unwrap_or(0);
+    eprintln!(
+        "fan-out: max={max_fan_out}, mean={:.2}",
+        fan_out.iter().sum::<usize>() as f64 / fan_out.len().max(1) as f64
+    );
+
+    let cycles = graph.find_cycles();
+    if cycles.is_empty() {
+        eprintln!("no cycles detected");
+    } else {
+        for cycle in &cycles {
+            let names: Vec<&str> = cycle
+                .chain
+                .iter()
+                .map(|&i| crate_display_name(&project, i))
+                .collect();
+            eprintln!("cycle detected: {}", names.join(" -> "));
+        }
+    }
+
+    let prioritize: Vec<bool> = project
+        .crates
+        .iter()
+        .map(|krate| !krate.is_workspace_member)
+        .collect();
+    let order = graph.topological_order(&prioritize);
+    eprintln!(
+        "topological load order covers {}/{} crates ({} left out of cyclic components)",
+        order.len(),
+        graph.node_count(),
+        graph.node_count() - order.len()
+    );
+
+    let crates: Vec<CrateWalk> = order
+        .iter()
+        .map(|&index| {
+            let krate = &project.crates[index];
+            CrateWalk {
+                name: crate_display_name(&project, index).to_string(),
+                roots: krate.source_roots(),
+                excludes: krate.exclude_dirs().clone(),
+            }
+        })
+        .collect();
+
+    let instant = Instant::now();
+    let (projects, stats) = load_dedup(crates);
+    let wall_time = instant.elapsed();
+
+    let sysroot = report_sysroot(&project);
+
+    let members = member_lookup(&project);
+    let report = BenchmarkReport::new(
+        "graph",
+        wall_time,
+        crate_reports(&projects, |name| {
+            members.get(name).copied().unwrap_or(true)
+        }),
+        &stats,
+        Some(sysroot),
+    );
+    output.emit(&report)
 }
 
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub(crate) struct Dep {
-    #[serde(rename = "crate")]
-    pub(crate) crate_index: usize,
-    pub(crate) name: String,
+fn crate_display_name(project: &JsonProject, index: usize) -> &str {
+    project.crates[index]
+        .display_name
+        .as_deref()
+        .unwrap_or("<unnamed>")
 }
 
-/// Sysroot paths. These are documented in the rust-analyzer manual:
-///
-/// <https://rust-analyzer.github.io/manual.html#non-cargo-based-projects>
-///
-/// rust-analyzer treats both paths as optional, but we always provide sysroot.
-#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
-pub(crate) struct Sysroot {
-    /// Path to the directory of the sysroot; this is a superset of `sysroot_src`.
-    ///
-    /// This path provides rust-analyzer both the *source code* of libraries
-    /// like `std` and `core` and binaries like `rust-analyzer-proc-macro-srv`,
-    /// which enable rust-analyzer to expand procedural macros.
-    ///
-    /// For example, a `sysroot` is `~/fbsource/fbcode/third-party-buck/platform010/build/rust/`.
-    ///
-    /// `rust-analyzer` relies on an external binary to expand procedural
-    /// macros and the source code location can be predictably inferred.
-    /// Assuming the example sysroot above, the source code would be located in
-    /// `/lib/rustlib/src/rust/`.
-    pub(crate) sysroot: PathBuf,
-    /// Legacy sysroot config containing only the source code of libraries such
-    /// as `std` and core`.
-    ///
-    /// Inside Meta, this is necessary on non-Linux platforms since the sources
-    /// are packaged seperately from binaries such as `rust-analyzer-proc-macro-srv`.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub(crate) sysroot_src: Option<PathBuf>,
+fn handle_watch(path: &Path) -> Result<(), anyhow::Error> {
+    let s = std::fs::read_to_string(path)?;
+    let project: JsonProject = serde_json::from_str(&s)?;
+
+    watch::watch(&project)
 }
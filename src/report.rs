@@ -0,0 +1,132 @@
+use crate::load::DedupStats;
+
+use serde::Serialize;
+use std::{
+    path::{Path, PathBuf},
+    time::Duration,
+};
+
+/// Per-crate load results: enough to tell workspace vs. sysroot/third-party
+/// load costs apart across runs.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct CrateReport {
+    pub(crate) name: String,
+    pub(crate) is_workspace_member: bool,
+    pub(crate) file_count: usize,
+    pub(crate) bytes_read: u64,
+    pub(crate) read_time_ms: u128,
+    /// One entry per file that failed to read; empty when every file in this
+    /// crate's set was read successfully.
+    pub(crate) errors: Vec<String>,
+}
+
+/// Sysroot load cost and proc-macro availability, reported separately from
+/// `crates` since the sysroot isn't walked through the same `rust-project.json`
+/// crate list (and `cargo`-driven runs have no sysroot data at all).
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct SysrootReport {
+    pub(crate) load_time_ms: u128,
+    pub(crate) file_count: usize,
+    pub(crate) bytes_read: u64,
+    pub(crate) proc_macro_dylibs_present: usize,
+    pub(crate) proc_macro_dylibs_missing: Vec<String>,
+    pub(crate) proc_macro_srv_path: Option<PathBuf>,
+}
+
+/// A full benchmark run, serialized as-is via `serde_json` so results can be
+/// diffed across runs instead of thrown away after printing one number.
+#[derive(Debug, Clone, Serialize)]
+pub(crate) struct BenchmarkReport {
+    pub(crate) command: String,
+    pub(crate) generated_at_unix_ms: u128,
+    pub(crate) wall_time_ms: u128,
+    pub(crate) crates: Vec<CrateReport>,
+    pub(crate) dedup_files_saved: usize,
+    pub(crate) dedup_bytes_saved: u64,
+    pub(crate) sysroot: Option<SysrootReport>,
+}
+
+impl BenchmarkReport {
+    pub(crate) fn new(
+        command: &str,
+        wall_time: Duration,
+        crates: Vec<CrateReport>,
+        dedup: &DedupStats,
+        sysroot: Option<SysrootReport>,
+    ) -> BenchmarkReport {
+        let generated_at_unix_ms = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis();
+
+        BenchmarkReport {
+            command: command.to_string(),
+            generated_at_unix_ms,
+            wall_time_ms: wall_time.as_millis(),
+            crates,
+            dedup_files_saved: dedup.files_saved(),
+            dedup_bytes_saved: dedup.bytes_saved(),
+            sysroot,
+        }
+    }
+
+    pub(crate) fn print_human(&self) {
+        eprintln!(
+            "{}: {} crates, {}ms wall time",
+            self.command,
+            self.crates.len(),
+            self.wall_time_ms
+        );
+        for krate in &self.crates {
+            let member = if krate.is_workspace_member {
+                "member"
+            } else {
+                "non-member"
+            };
+            eprintln!(
+                "  {} [{member}]: {} files, {} bytes, {}ms",
+                krate.name, krate.file_count, krate.bytes_read, krate.read_time_ms
+            );
+            for error in &krate.errors {
+                eprintln!("    ERROR {error}");
+            }
+        }
+        eprintln!(
+            "dedup saved {} files ({} bytes) vs. a naive per-crate walk",
+            self.dedup_files_saved, self.dedup_bytes_saved
+        );
+
+        if let Some(sysroot) = &self.sysroot {
+            eprintln!(
+                "sysroot load: {}ms, {} files, {} bytes",
+                sysroot.load_time_ms, sysroot.file_count, sysroot.bytes_read
+            );
+            eprintln!(
+                "proc-macro dylibs: {} present, {} missing ({:?})",
+                sysroot.proc_macro_dylibs_present,
+                sysroot.proc_macro_dylibs_missing.len(),
+                sysroot.proc_macro_dylibs_missing
+            );
+            match &sysroot.proc_macro_srv_path {
+                Some(path) => eprintln!("proc-macro-srv found at {}", path.display()),
+                None => eprintln!(
+                    "proc-macro-srv not found under sysroot; macro expansion unavailable"
+                ),
+            }
+        }
+    }
+
+    pub(crate) fn write_json(&self, path: &Path) -> Result<(), anyhow::Error> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json)?;
+        Ok(())
+    }
+}
+
+/// Output format for a finished `BenchmarkReport`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub(crate) enum Format {
+    Json,
+    #[default]
+    Human,
+}
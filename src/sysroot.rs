@@ -0,0 +1,195 @@
+use crate::load::{load_dedup, CrateWalk, DedupStats};
+use crate::project::{Crate, Sysroot};
+
+use std::{
+    path::{Path, PathBuf},
+    time::{Duration, Instant},
+};
+
+/// Standard-library crates rust-analyzer always loads out of the sysroot.
+const SYSROOT_CRATES: &[&str] = &["std", "core", "alloc", "proc_macro", "test"];
+
+const PROC_MACRO_SRV_BIN: &str = "rust-analyzer-proc-macro-srv";
+
+/// The result of reading every sysroot crate's source tree, kept separate
+/// from first-party crate loads so the two can be compared.
+pub(crate) struct SysrootLoad {
+    pub(crate) stats: DedupStats,
+    pub(crate) elapsed: Duration,
+}
+
+/// Picks the directory that actually holds the `std`/`core`/... sources:
+/// `sysroot_src` when the project sets it, otherwise the conventional
+/// `lib/rustlib/src/rust/library` layout underneath `sysroot`.
+pub(crate) fn sysroot_src_dir(sysroot: &Sysroot) -> PathBuf {
+    sysroot
+        .sysroot_src
+        .clone()
+        .unwrap_or_else(|| sysroot.sysroot.join("lib/rustlib/src/rust/library"))
+}
+
+/// Reads every standard-library crate's source tree, folding the time spent
+/// into a "sysroot load" figure reported separately from first-party crates.
+pub(crate) fn load_sysroot(sysroot: &Sysroot) -> SysrootLoad {
+    let library = sysroot_src_dir(sysroot);
+    let crates: Vec<CrateWalk> = SYSROOT_CRATES
+        .iter()
+        .map(|name| CrateWalk {
+            name: (*name).to_string(),
+            roots: vec![library.join(name)],
+            excludes: Default::default(),
+        })
+        .collect();
+
+    let instant = Instant::now();
+    let (_, stats) = load_dedup(crates);
+    let elapsed = instant.elapsed();
+
+    SysrootLoad { stats, elapsed }
+}
+
+/// Whether proc-macro crates have a dylib on disk, and whether the
+/// proc-macro expansion server itself can be found under the sysroot.
+pub(crate) struct ProcMacroReport {
+    pub(crate) present: usize,
+    pub(crate) missing: Vec<String>,
+    pub(crate) server_path: Option<PathBuf>,
+}
+
+/// For every `is_proc_macro` crate, stats `proc_macro_dylib_path` to see
+/// whether the compiled dylib actually exists, and probes
+/// `<sysroot>/libexec` and `<sysroot>/bin` for the `rust-analyzer-proc-macro-srv`
+/// binary rust-analyzer shells out to for macro expansion.
+pub(crate) fn check_proc_macros(crates: &[Crate], sysroot: &Sysroot) -> ProcMacroReport {
+    let mut present = 0;
+    let mut missing = Vec::new();
+    for krate in crates.iter().filter(|krate| krate.is_proc_macro) {
+        let exists = krate
+            .proc_macro_dylib_path
+            .as_deref()
+            .map(Path::exists)
+            .unwrap_or(false);
+        if exists {
+            present += 1;
+        } else {
+            missing.push(krate.display_name.clone().unwrap_or_default());
+        }
+    }
+
+    let server_path = [
+        sysroot.sysroot.join("libexec"),
+        sysroot.sysroot.join("bin"),
+    ]
+    .into_iter()
+    .map(|dir| dir.join(PROC_MACRO_SRV_BIN))
+    .find(|path| path.exists());
+
+    ProcMacroReport {
+        present,
+        missing,
+        server_path,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(tag: &str) -> PathBuf {
+        let nanos = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos();
+        let dir = std::env::temp_dir().join(format!("sysroot-test-{tag}-{nanos}"));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sysroot_src_dir_uses_sysroot_src_when_set() {
+        let sysroot = Sysroot {
+            sysroot: PathBuf::from("/opt/rust"),
+            sysroot_src: Some(PathBuf::from("/opt/rust-src/library")),
+        };
+        assert_eq!(sysroot_src_dir(&sysroot), PathBuf::from("/opt/rust-src/library"));
+    }
+
+    #[test]
+    fn sysroot_src_dir_falls_back_to_the_conventional_layout() {
+        let sysroot = Sysroot {
+            sysroot: PathBuf::from("/opt/rust"),
+            sysroot_src: None,
+        };
+        assert_eq!(
+            sysroot_src_dir(&sysroot),
+            PathBuf::from("/opt/rust/lib/rustlib/src/rust/library")
+        );
+    }
+
+    #[test]
+    fn check_proc_macros_counts_present_and_missing_dylibs() {
+        let dir = temp_dir("dylibs");
+        let present_dylib = dir.join("present.so");
+        std::fs::write(&present_dylib, b"").unwrap();
+
+        let crates = vec![
+            Crate {
+                is_proc_macro: true,
+                proc_macro_dylib_path: Some(present_dylib),
+                ..Default::default()
+            },
+            Crate {
+                is_proc_macro: true,
+                display_name: Some("missing-macro".to_string()),
+                proc_macro_dylib_path: Some(dir.join("missing.so")),
+                ..Default::default()
+            },
+            Crate {
+                is_proc_macro: false,
+                ..Default::default()
+            },
+        ];
+        let sysroot = Sysroot {
+            sysroot: dir.clone(),
+            sysroot_src: None,
+        };
+
+        let report = check_proc_macros(&crates, &sysroot);
+        assert_eq!(report.present, 1);
+        assert_eq!(report.missing, vec!["missing-macro".to_string()]);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn check_proc_macros_finds_the_srv_binary_under_libexec() {
+        let dir = temp_dir("srv");
+        let libexec = dir.join("libexec");
+        std::fs::create_dir_all(&libexec).unwrap();
+        std::fs::write(libexec.join(PROC_MACRO_SRV_BIN), b"").unwrap();
+
+        let sysroot = Sysroot {
+            sysroot: dir.clone(),
+            sysroot_src: None,
+        };
+
+        let report = check_proc_macros(&[], &sysroot);
+        assert_eq!(report.server_path, Some(libexec.join(PROC_MACRO_SRV_BIN)));
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn check_proc_macros_reports_no_srv_binary_when_absent() {
+        let dir = temp_dir("no-srv");
+        let sysroot = Sysroot {
+            sysroot: dir.clone(),
+            sysroot_src: None,
+        };
+
+        let report = check_proc_macros(&[], &sysroot);
+        assert_eq!(report.server_path, None);
+
+        std::fs::remove_dir_all(&dir).ok();
+    }
+}
@@ -0,0 +1,117 @@
+use crate::project::{Crate, JsonProject};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use rustc_hash::{FxHashMap, FxHashSet};
+use std::{
+    path::{Path, PathBuf},
+    sync::mpsc::{self, RecvTimeoutError},
+    time::{Duration, Instant},
+};
+use walkdir::WalkDir;
+
+/// Bursts of filesystem events arriving within this window are coalesced
+/// into a single re-read pass, keyed by canonical path.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+
+/// `cache` is keyed by canonical path, but a delete event's path no longer
+/// exists, so `path.canonicalize()` itself fails on it. Canonicalize the
+/// parent directory instead and re-attach the file name, so a delete under a
+/// symlinked root (e.g. Buck/Bazel's `buck-out`/`bazel-out`) still maps back
+/// to the same key the file was cached under.
+fn canonicalize_deleted(path: &Path) -> Option<PathBuf> {
+    let file_name = path.file_name()?;
+    let parent = path.parent()?;
+    Some(parent.canonicalize().ok()?.join(file_name))
+}
+
+/// Performs the initial bulk load, then watches every crate root (and
+/// `include_dirs`) for create/modify/delete events, re-reading just the
+/// affected paths. This mirrors rust-analyzer's VFS: a one-shot load
+/// followed by steady-state incremental re-ingestion.
+pub(crate) fn watch(project: &JsonProject) -> Result<(), anyhow::Error> {
+    let roots: Vec<PathBuf> = project
+        .crates
+        .iter()
+        .flat_map(Crate::source_roots)
+        .collect();
+
+    let instant = Instant::now();
+    let mut cache: FxHashMap<PathBuf, String> = FxHashMap::default();
+    for root in &roots {
+        for entry in WalkDir::new(root)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+        {
+            if let Ok(path) = entry.path().canonicalize() {
+                if let Ok(contents) = std::fs::read_to_string(&path) {
+                    cache.insert(path, contents);
+                }
+            }
+        }
+    }
+    eprintln!(
+        "initial load: {}ms, {} files",
+        instant.elapsed().as_millis(),
+        cache.len()
+    );
+
+    let (tx, rx) = mpsc::channel::<notify::Result<Event>>();
+    let mut watcher: RecommendedWatcher = notify::recommended_watcher(tx)?;
+    for root in &roots {
+        watcher.watch(root, RecursiveMode::Recursive)?;
+    }
+
+    let mut pending: FxHashSet<PathBuf> = FxHashSet::default();
+    let mut total_reads = 0usize;
+    loop {
+        match rx.recv_timeout(DEBOUNCE) {
+            Ok(Ok(event)) => {
+                for path in event.paths {
+                    match path.canonicalize() {
+                        Ok(canonical) => {
+                            pending.insert(canonical);
+                        }
+                        // the file no longer exists (a delete); evict it under
+                        // the same canonical key it was cached under.
+                        Err(_) => {
+                            if let Some(canonical) = canonicalize_deleted(&path) {
+                                cache.remove(&canonical);
+                            } else {
+                                cache.remove(&path);
+                            }
+                        }
+                    }
+                }
+            }
+            Ok(Err(e)) => eprintln!("watch error: {e}"),
+            Err(RecvTimeoutError::Timeout) => {
+                if pending.is_empty() {
+                    continue;
+                }
+
+                let batch: Vec<PathBuf> = pending.drain().collect();
+                let instant = Instant::now();
+                for path in &batch {
+                    match std::fs::read_to_string(path) {
+                        Ok(contents) => {
+                            cache.insert(path.clone(), contents);
+                        }
+                        Err(_) => {
+                            cache.remove(path);
+                        }
+                    }
+                }
+                total_reads += batch.len();
+                eprintln!(
+                    "re-read {} file(s) in {}us (total re-reads: {total_reads})",
+                    batch.len(),
+                    instant.elapsed().as_micros()
+                );
+            }
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+    }
+
+    Ok(())
+}